@@ -2,17 +2,233 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 use tauri::Manager;
+use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 
-#[tauri::command]
-fn get_ws_port() -> u16 {
-    // Debug: Print all args
+/// Maps a content hash to the on-disk path of the file and its thumbnail.
+#[derive(Default)]
+struct MediaIndex {
+    files: Mutex<HashMap<String, PathBuf>>,
+    thumbnails: Mutex<HashMap<String, PathBuf>>,
+}
+
+const DEFAULT_SHOW_ACCELERATOR: &str = "CmdOrCtrl+Shift+M";
+const DEFAULT_RESCAN_ACCELERATOR: &str = "CmdOrCtrl+Shift+R";
+
+/// Accelerators currently bound to the "show window" and "rescan" shortcuts.
+struct GlobalShortcuts {
+    show: Mutex<String>,
+    rescan: Mutex<String>,
+}
+
+impl Default for GlobalShortcuts {
+    fn default() -> Self {
+        Self {
+            // Nothing is bound until `setup()` registers the defaults.
+            show: Mutex::new(String::new()),
+            rescan: Mutex::new(String::new()),
+        }
+    }
+}
+
+/// Best-effort MIME type detection based on file extension.
+fn guess_mime_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase().as_str() {
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "bmp" => "image/bmp",
+        "mp4" => "video/mp4",
+        "mov" => "video/quicktime",
+        "avi" => "video/x-msvideo",
+        "mkv" => "video/x-matroska",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Strips the `media://`/`https://media.localhost/` prefix and leading
+/// `thumb/` segment, returning `(is_thumbnail, hash)`.
+fn parse_media_uri(uri: &str) -> (bool, String) {
+    let rest = uri
+        .strip_prefix("media://")
+        .or_else(|| uri.strip_prefix("https://media.localhost/"))
+        .unwrap_or(uri);
+    match rest.strip_prefix("thumb/") {
+        Some(hash) => (true, hash.trim_end_matches('/').to_string()),
+        None => (false, rest.trim_end_matches('/').to_string()),
+    }
+}
+
+/// An inclusive byte range requested via an HTTP `Range: bytes=start-end` header.
+struct ByteRange {
+    start: u64,
+    end: u64,
+}
+
+/// Parses a `bytes=start-end` (or suffix `bytes=-N`) range header. `Ok(None)`
+/// means no range was requested; `Err` means 416.
+fn parse_range_header(header: Option<&str>, file_size: u64) -> Result<Option<ByteRange>, ()> {
+    let header = match header {
+        Some(h) => h,
+        None => return Ok(None),
+    };
+
+    let spec = header.strip_prefix("bytes=").ok_or(())?;
+    let (start_str, end_str) = spec.split_once('-').ok_or(())?;
+
+    // `bytes=-500` is a suffix range: the last 500 bytes of the file.
+    if start_str.is_empty() {
+        let suffix_len: u64 = end_str.parse().map_err(|_| ())?;
+        if suffix_len == 0 {
+            return Err(());
+        }
+        let start = file_size.saturating_sub(suffix_len);
+        return Ok(Some(ByteRange { start, end: file_size.saturating_sub(1) }));
+    }
+
+    let start: u64 = start_str.parse().map_err(|_| ())?;
+    let end: u64 = if end_str.is_empty() {
+        file_size.saturating_sub(1)
+    } else {
+        end_str.parse().map_err(|_| ())?
+    };
+
+    if start > end || start >= file_size {
+        return Err(());
+    }
+
+    Ok(Some(ByteRange { start, end: end.min(file_size.saturating_sub(1)) }))
+}
+
+/// Builds the HTTP response for a `media://` request, honoring `Range` with a
+/// 206 partial response when present.
+fn serve_media_request(
+    path: Option<PathBuf>,
+    range_header: Option<String>,
+    hash: &str,
+) -> tauri::http::Response {
+    let path = match path {
+        Some(path) => path,
+        None => {
+            return tauri::http::ResponseBuilder::new()
+                .status(404)
+                .mimetype("text/plain")
+                .body(format!("no media found for hash {}", hash).into_bytes())
+                .unwrap();
+        }
+    };
+
+    let mut file = match fs::File::open(&path) {
+        Ok(file) => file,
+        Err(e) => {
+            return tauri::http::ResponseBuilder::new()
+                .status(404)
+                .mimetype("text/plain")
+                .body(format!("failed to open media: {}", e).into_bytes())
+                .unwrap();
+        }
+    };
+
+    let file_size = match file.metadata() {
+        Ok(meta) => meta.len(),
+        Err(e) => {
+            return tauri::http::ResponseBuilder::new()
+                .status(500)
+                .mimetype("text/plain")
+                .body(format!("failed to stat media: {}", e).into_bytes())
+                .unwrap();
+        }
+    };
+
+    let mime_type = guess_mime_type(&path);
+
+    match parse_range_header(range_header.as_deref(), file_size) {
+        Ok(Some(range)) => {
+            let len = range.end - range.start + 1;
+            let mut buf = vec![0u8; len as usize];
+            if let Err(e) = file.seek(SeekFrom::Start(range.start)) {
+                return tauri::http::ResponseBuilder::new()
+                    .status(500)
+                    .mimetype("text/plain")
+                    .body(format!("failed to seek media: {}", e).into_bytes())
+                    .unwrap();
+            }
+            if let Err(e) = file.read_exact(&mut buf) {
+                return tauri::http::ResponseBuilder::new()
+                    .status(500)
+                    .mimetype("text/plain")
+                    .body(format!("failed to read media: {}", e).into_bytes())
+                    .unwrap();
+            }
+
+            tauri::http::ResponseBuilder::new()
+                .status(206)
+                .mimetype(mime_type)
+                .header("Accept-Ranges", "bytes")
+                .header("Content-Range", format!("bytes {}-{}/{}", range.start, range.end, file_size))
+                .header("Content-Length", len.to_string())
+                .body(buf)
+                .unwrap()
+        }
+        Ok(None) => {
+            let mut buf = Vec::with_capacity(file_size as usize);
+            if let Err(e) = file.read_to_end(&mut buf) {
+                return tauri::http::ResponseBuilder::new()
+                    .status(500)
+                    .mimetype("text/plain")
+                    .body(format!("failed to read media: {}", e).into_bytes())
+                    .unwrap();
+            }
+
+            tauri::http::ResponseBuilder::new()
+                .status(200)
+                .mimetype(mime_type)
+                .header("Accept-Ranges", "bytes")
+                .body(buf)
+                .unwrap()
+        }
+        Err(()) => tauri::http::ResponseBuilder::new()
+            .status(416)
+            .mimetype("text/plain")
+            .header("Content-Range", format!("bytes */{}", file_size))
+            .body(Vec::new())
+            .unwrap(),
+    }
+}
+
+/// Pulls a folder path to scan from a `--scan=<path>` flag or a bare argument
+/// that points at an existing directory.
+fn parse_scan_path(argv: &[String]) -> Option<String> {
+    argv.iter().find_map(|arg| {
+        if let Some(path) = arg.strip_prefix("--scan=") {
+            Some(path.to_string())
+        } else if Path::new(arg).is_dir() {
+            Some(arg.clone())
+        } else {
+            None
+        }
+    })
+}
+
+/// The WebSocket port negotiated at startup.
+struct WsPort {
+    port: u16,
+}
+
+/// Binds the WebSocket listener, honoring a `--ws-port=`/`TAURI_WS_PORT`
+/// override or else letting the OS pick a free port, and returns it alongside
+/// the port it landed on.
+fn resolve_ws_port() -> (std::net::TcpListener, u16) {
     let args: Vec<String> = std::env::args().collect();
     println!("Command line args: {:?}", args);
 
-    // Get port from command line args or environment
-    let port = std::env::args().nth(1)
+    let override_port = std::env::args().nth(1)
         .and_then(|arg| {
             println!("Processing arg: {}", arg);
             // Parse --ws-port=8765 format
@@ -32,80 +248,270 @@ fn get_ws_port() -> u16 {
             } else {
                 None
             }
-        })
-        .unwrap_or(8765);
+        });
+
+    // Bind to the override if given, otherwise to port 0 so the OS picks a
+    // free ephemeral port. Either way this is the listener the app keeps.
+    let bind_addr = format!("127.0.0.1:{}", override_port.unwrap_or(0));
+    let listener = std::net::TcpListener::bind(&bind_addr)
+        .unwrap_or_else(|e| panic!("failed to bind websocket listener on {}: {}", bind_addr, e));
+    let port = listener.local_addr().expect("bound listener has a local address").port();
 
     println!("Final WebSocket port: {}", port);
+    (listener, port)
+}
 
-    // Write the port to a config file
-    write_ws_config(port);
-
-    port
-}
-
-fn write_ws_config(port: u16) {
-    // Get the path to the src directory
-    let exe_path = std::env::current_exe().unwrap();
-    let exe_dir = exe_path.parent().unwrap();
-
-    // Go up to find the src directory (from target/release to src)
-    let src_path = exe_dir
-        .parent() // target
-        .and_then(|p| p.parent()) // src-tauri
-        .and_then(|p| p.parent()) // tauri-map
-        .map(|p| p.join("src").join("ws-config.js"));
-
-    if let Some(config_path) = src_path {
-        let config_content = format!(
-            "// Auto-generated WebSocket configuration\n\
-             window.WS_CONFIG = {{\n\
-             \tport: {},\n\
-             \ttimestamp: '{}'\n\
-             }};\n\
-             console.log('[ws-config.js] WebSocket port configured:', {});",
-            port,
-            chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
-            port
-        );
-
-        if let Err(e) = fs::write(&config_path, config_content) {
-            eprintln!("Failed to write ws-config.js: {}", e);
-        } else {
-            println!("WebSocket config written to: {:?}", config_path);
+/// Accepts and upgrades connections on the negotiated WebSocket listener for
+/// the life of the app.
+fn spawn_websocket_server(listener: std::net::TcpListener) {
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    eprintln!("WebSocket accept error: {}", e);
+                    continue;
+                }
+            };
+
+            std::thread::spawn(move || {
+                let mut socket = match tungstenite::accept(stream) {
+                    Ok(socket) => socket,
+                    Err(e) => {
+                        eprintln!("WebSocket handshake failed: {}", e);
+                        return;
+                    }
+                };
+
+                loop {
+                    match socket.read() {
+                        Ok(msg) if msg.is_close() => break,
+                        Ok(_) => {}
+                        Err(_) => break,
+                    }
+                }
+            });
         }
+    });
+}
+
+#[tauri::command]
+fn get_ws_port(window: tauri::Window, ws_port: tauri::State<WsPort>) -> Result<u16, String> {
+    if !is_trusted_origin(&window) {
+        return Err("get_ws_port is only available to the app's local origin".to_string());
+    }
+    Ok(ws_port.port)
+}
+
+/// Whether the calling window is showing the app's own local content rather
+/// than a remote origin.
+fn is_trusted_origin(window: &tauri::Window) -> bool {
+    let url = match window.url() {
+        Ok(url) => url,
+        Err(_) => return false,
+    };
+
+    match url.scheme() {
+        "tauri" | "asset" => true,
+        "https" if url.host_str() == Some("tauri.localhost") => true,
+        "http" if cfg!(debug_assertions) && matches!(url.host_str(), Some("localhost") | Some("127.0.0.1")) => true,
+        _ => false,
     }
 }
 
 #[tauri::command]
-fn get_map_config() -> serde_json::Value {
-    serde_json::json!({
+fn get_map_config(
+    window: tauri::Window,
+    ws_port: tauri::State<WsPort>,
+    shortcuts: tauri::State<GlobalShortcuts>,
+) -> Result<serde_json::Value, String> {
+    if !is_trusted_origin(&window) {
+        return Err("get_map_config is only available to the app's local origin".to_string());
+    }
+
+    Ok(serde_json::json!({
         "mapboxToken": std::env::var("MAPBOX_TOKEN").ok(),
-        "wsPort": get_ws_port()
-    })
+        "wsPort": ws_port.port,
+        "showShortcut": shortcuts.show.lock().unwrap().clone(),
+        "rescanShortcut": shortcuts.rescan.lock().unwrap().clone()
+    }))
+}
+
+/// Same as `get_map_config` but without `mapboxToken`.
+#[tauri::command]
+fn get_public_map_config(
+    window: tauri::Window,
+    ws_port: tauri::State<WsPort>,
+    shortcuts: tauri::State<GlobalShortcuts>,
+) -> Result<serde_json::Value, String> {
+    if !is_trusted_origin(&window) {
+        return Err("get_public_map_config is only available to the app's local origin".to_string());
+    }
+
+    Ok(serde_json::json!({
+        "wsPort": ws_port.port,
+        "showShortcut": shortcuts.show.lock().unwrap().clone(),
+        "rescanShortcut": shortcuts.rescan.lock().unwrap().clone()
+    }))
+}
+
+/// IPC-facing wrapper around `set_global_shortcut_internal` gated to the
+/// app's local origin.
+#[tauri::command]
+fn set_global_shortcut(
+    window: tauri::Window,
+    app: tauri::AppHandle,
+    shortcuts: tauri::State<GlobalShortcuts>,
+    kind: String,
+    accelerator: String,
+) -> Result<(), String> {
+    if !is_trusted_origin(&window) {
+        return Err("set_global_shortcut is only available to the app's local origin".to_string());
+    }
+    set_global_shortcut_internal(&app, &shortcuts, kind, accelerator)
+}
+
+/// Rebinds the "show window" or "rescan" global shortcut. Also used directly
+/// to register the default accelerators during `setup()`.
+fn set_global_shortcut_internal(
+    app: &tauri::AppHandle,
+    shortcuts: &GlobalShortcuts,
+    kind: String,
+    accelerator: String,
+) -> Result<(), String> {
+    use tauri::GlobalShortcutManager;
+
+    let mut manager = app.global_shortcut_manager();
+    let current = match kind.as_str() {
+        "show" => &shortcuts.show,
+        "rescan" => &shortcuts.rescan,
+        other => return Err(format!("unknown shortcut kind: {}", other)),
+    };
+
+    let mut current = current.lock().unwrap();
+    if *current == accelerator {
+        return Ok(());
+    }
+
+    let app_for_shortcut = app.clone();
+    let kind_for_shortcut = kind.clone();
+    manager
+        .register(&accelerator, move || {
+            if kind_for_shortcut == "show" {
+                if let Some(window) = app_for_shortcut.get_window("main") {
+                    window.unminimize().ok();
+                    window.show().ok();
+                    window.set_focus().ok();
+                }
+            } else {
+                app_for_shortcut.emit_all("rescan", ()).ok();
+            }
+        })
+        .map_err(|e| e.to_string())?;
+
+    // Only drop the previous binding once the new one is confirmed live, so a
+    // failed registration above leaves the existing shortcut intact instead
+    // of leaving the user with neither.
+    manager.unregister(&current).ok();
+
+    *current = accelerator;
+    Ok(())
 }
 
 fn main() {
-    // Get the WebSocket port from command line or environment
-    // This also writes the config file
-    let ws_port = get_ws_port();
+    // Negotiate the WebSocket port from command line/environment override or,
+    // failing that, let the OS pick a free one, then start serving on it.
+    let (ws_listener, ws_port) = resolve_ws_port();
+    spawn_websocket_server(ws_listener);
 
     println!("Starting Tauri with WebSocket port: {}", ws_port);
 
     tauri::Builder::default()
-        .invoke_handler(tauri::generate_handler![get_ws_port, get_map_config])
+        .plugin(tauri_plugin_single_instance::init(|app, argv, _cwd| {
+            println!("Single-instance callback, argv: {:?}", argv);
+
+            if let Some(window) = app.get_window("main") {
+                window.unminimize().ok();
+                window.show().ok();
+                window.set_focus().ok();
+            }
+
+            if let Some(scan_path) = parse_scan_path(&argv) {
+                println!("Forwarding scan request to running instance: {}", scan_path);
+                app.emit_all("scan-requested", scan_path).ok();
+            }
+        }))
+        .manage(WsPort { port: ws_port })
+        .manage(MediaIndex::default())
+        .manage(GlobalShortcuts::default())
+        .register_asynchronous_uri_scheme_protocol("media", |app, request, responder| {
+            let (is_thumbnail, hash) = parse_media_uri(request.uri());
+            let range_header = request
+                .headers()
+                .get("range")
+                .and_then(|v| v.to_str().ok())
+                .map(|s| s.to_string());
+
+            let index = app.state::<MediaIndex>();
+            let lookup = if is_thumbnail { &index.thumbnails } else { &index.files };
+            let path = lookup.lock().unwrap().get(&hash).cloned();
+
+            tauri::async_runtime::spawn(async move {
+                // serve_media_request does blocking file I/O; run it on the
+                // blocking pool so it doesn't stall the async worker thread
+                // while scrubbing through large forensic video files.
+                let response = tauri::async_runtime::spawn_blocking(move || {
+                    serve_media_request(path, range_header, &hash)
+                })
+                .await
+                .unwrap_or_else(|e| {
+                    tauri::http::ResponseBuilder::new()
+                        .status(500)
+                        .mimetype("text/plain")
+                        .body(format!("media task panicked: {}", e).into_bytes())
+                        .unwrap()
+                });
+                responder.respond(response);
+            });
+        })
+        .invoke_handler(tauri::generate_handler![
+            get_ws_port,
+            get_map_config,
+            get_public_map_config,
+            set_global_shortcut
+        ])
         .setup(move |app| {
             use tauri::Manager;
 
-            // Navigate to mapbox.html with port parameter
+            // Register the default global shortcuts so analysts can summon the
+            // app and trigger a rescan without alt-tabbing out of a full-screen map.
+            {
+                let app_handle = app.handle();
+                set_global_shortcut_internal(
+                    &app_handle,
+                    &app_handle.state::<GlobalShortcuts>(),
+                    "show".to_string(),
+                    DEFAULT_SHOW_ACCELERATOR.to_string(),
+                )
+                .unwrap_or_else(|e| eprintln!("Failed to register show shortcut: {}", e));
+                set_global_shortcut_internal(
+                    &app_handle,
+                    &app_handle.state::<GlobalShortcuts>(),
+                    "rescan".to_string(),
+                    DEFAULT_RESCAN_ACCELERATOR.to_string(),
+                )
+                .unwrap_or_else(|e| eprintln!("Failed to register rescan shortcut: {}", e));
+            }
+
+            // Navigate to mapbox.html; the page fetches its WebSocket port and
+            // Mapbox config itself via `invoke("get_map_config")` once loaded,
+            // so there's no need to thread the port through the URL.
             if let Some(window) = app.get_window("main") {
-                // Navigate with port parameter
                 let window_nav = window.clone();
-                let port_for_nav = ws_port;
                 std::thread::spawn(move || {
                     std::thread::sleep(std::time::Duration::from_millis(100));
-                    let script = format!("window.location.href = 'mapbox.html?port={}'", port_for_nav);
-                    window_nav.eval(&script).ok();
-                    println!("Navigated to mapbox.html with port: {}", port_for_nav);
+                    window_nav.eval("window.location.href = 'mapbox.html'").ok();
+                    println!("Navigated to mapbox.html");
                 });
 
                 // Automatically open DevTools in release builds for debugging
@@ -123,4 +529,78 @@ fn main() {
         })
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_range_header_serves_whole_file() {
+        assert!(parse_range_header(None, 1000).unwrap().is_none());
+    }
+
+    #[test]
+    fn open_ended_range_serves_to_end_of_file() {
+        let range = parse_range_header(Some("bytes=500-"), 1000).unwrap().unwrap();
+        assert_eq!(range.start, 500);
+        assert_eq!(range.end, 999);
+    }
+
+    #[test]
+    fn bounded_range_is_used_as_is() {
+        let range = parse_range_header(Some("bytes=0-499"), 1000).unwrap().unwrap();
+        assert_eq!(range.start, 0);
+        assert_eq!(range.end, 499);
+    }
+
+    #[test]
+    fn suffix_range_serves_last_n_bytes() {
+        let range = parse_range_header(Some("bytes=-500"), 1000).unwrap().unwrap();
+        assert_eq!(range.start, 500);
+        assert_eq!(range.end, 999);
+    }
+
+    #[test]
+    fn suffix_range_longer_than_file_clamps_to_start() {
+        let range = parse_range_header(Some("bytes=-5000"), 1000).unwrap().unwrap();
+        assert_eq!(range.start, 0);
+        assert_eq!(range.end, 999);
+    }
+
+    #[test]
+    fn suffix_range_of_zero_bytes_is_rejected() {
+        assert!(parse_range_header(Some("bytes=-0"), 1000).is_err());
+    }
+
+    #[test]
+    fn end_beyond_file_size_is_clamped() {
+        let range = parse_range_header(Some("bytes=0-99999"), 1000).unwrap().unwrap();
+        assert_eq!(range.end, 999);
+    }
+
+    #[test]
+    fn start_beyond_file_size_is_rejected() {
+        assert!(parse_range_header(Some("bytes=1000-1100"), 1000).is_err());
+    }
+
+    #[test]
+    fn start_after_end_is_rejected() {
+        assert!(parse_range_header(Some("bytes=500-100"), 1000).is_err());
+    }
+
+    #[test]
+    fn malformed_units_are_rejected() {
+        assert!(parse_range_header(Some("items=0-100"), 1000).is_err());
+    }
+
+    #[test]
+    fn malformed_numbers_are_rejected() {
+        assert!(parse_range_header(Some("bytes=a-b"), 1000).is_err());
+    }
+
+    #[test]
+    fn missing_dash_is_rejected() {
+        assert!(parse_range_header(Some("bytes=500"), 1000).is_err());
+    }
 }
\ No newline at end of file